@@ -1,66 +1,487 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[cfg(feature = "std")]
 use std::collections::HashMap;
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, string::ToString, vec, vec::IntoIter, vec::Vec};
+#[cfg(feature = "std")]
+use std::vec::IntoIter;
+
+#[cfg(feature = "std")]
 use std::fs;
+#[cfg(feature = "std")]
 use std::fs::OpenOptions;
-use std::io::prelude::*;
-use std::io::{BufReader, BufWriter, Error, ErrorKind, Result, Seek, SeekFrom, Write};
-use std::path::Path;
+#[cfg(feature = "std")]
+use std::io::BufWriter;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "std")]
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+#[cfg(not(feature = "std"))]
+use io_shim::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+use core::marker::PhantomData;
+
+/// minimal `no_std` stand-ins for the bits of `std::io` this crate uses
+/// (`Read`/`Write`/`Seek` plus their error types), vendored directly instead
+/// of pulling in an external `no_std` io crate, since the ones on the
+/// registry at the time of writing either don't build against current
+/// compilers or have been yanked
+#[cfg(not(feature = "std"))]
+pub mod io_shim {
+    use alloc::string::String;
+    use core::fmt;
+
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum ErrorKind {
+        UnexpectedEof,
+        InvalidData,
+        NotFound,
+        Other,
+    }
+
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: String,
+    }
+
+    impl Error {
+        pub fn new(kind: ErrorKind, message: &str) -> Self {
+            Self {
+                kind,
+                message: String::from(message),
+            }
+        }
+
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            let message = match kind {
+                ErrorKind::UnexpectedEof => "unexpected end of file",
+                ErrorKind::InvalidData => "invalid data",
+                ErrorKind::NotFound => "not found",
+                ErrorKind::Other => "other error",
+            };
+            Self::new(kind, message)
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.message)
+        }
+    }
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    pub enum SeekFrom {
+        Start(u64),
+        End(i64),
+        Current(i64),
+    }
+
+    pub trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.read(buf) {
+                    Ok(0) => break,
+                    Ok(n) => buf = &mut buf[n..],
+                    Err(err) => return Err(err),
+                }
+            }
+            if buf.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+        }
+    }
+
+    pub trait Write {
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+        fn flush(&mut self) -> Result<()>;
+
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf) {
+                    Ok(0) => {
+                        return Err(Error::new(ErrorKind::Other, "failed to write whole buffer"))
+                    }
+                    Ok(n) => buf = &buf[n..],
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    pub trait Seek {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+    }
+}
+
+/// size in bytes of the fixed part of a record: `crc32 | timestamp | store_id | key_size | val_size`
+const RECORD_HEADER_SIZE: usize = core::mem::size_of::<u32>()
+    + core::mem::size_of::<u64>()
+    + core::mem::size_of::<u64>()
+    + core::mem::size_of::<usize>() * 2;
+
+/// id of the store implicitly used by callers that never call `open_store`
+const DEFAULT_STORE_ID: u64 = 0;
+
+/// reserved store id used to persist the name->id registry backing
+/// [`Kvdb::open_store`]; never handed out to callers as a real [`Store`]
+const STORE_REGISTRY_ID: u64 = u64::MAX;
+
+/// magic bytes stamped at offset 0 of every file written by [`Kvdb::load`]
+#[cfg(feature = "std")]
+const FILE_MAGIC: [u8; 4] = *b"KVDB";
+
+/// on-disk record/file layout version written by this build
+#[cfg(feature = "std")]
+const FORMAT_VERSION: u16 = 1;
+
+/// size in bytes of the file-level header: `magic | version | flags`
+#[cfg(feature = "std")]
+const FILE_HEADER_SIZE: usize =
+    FILE_MAGIC.len() + core::mem::size_of::<u16>() + core::mem::size_of::<u16>();
+
+/// builds the file header this build stamps on freshly created files
+///
+/// the flags field is reserved (always 0) for now
+#[cfg(feature = "std")]
+fn file_header_bytes() -> [u8; FILE_HEADER_SIZE] {
+    let mut buf = [0u8; FILE_HEADER_SIZE];
+    buf[0..4].copy_from_slice(&FILE_MAGIC);
+    buf[4..6].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+    buf
+}
+
+/// IEEE 802.3 CRC-32, computed bitwise so the crate doesn't need a CRC dependency
+fn crc32(data: &[u8]) -> u32 {
+    const POLY: u32 = 0xEDB88320;
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (POLY & mask);
+        }
+    }
+    !crc
+}
+
+/// current unix time in milliseconds; `no_std` targets have no wall clock of
+/// their own, so builds without the `std` feature record 0 instead
+#[cfg(feature = "std")]
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+#[cfg(not(feature = "std"))]
+fn now_millis() -> u64 {
+    0
+}
+
+#[cfg(feature = "std")]
+fn read_u64_le(buf: &[u8], pos: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&buf[pos..pos + 8]);
+    u64::from_le_bytes(b)
+}
+
+#[cfg(feature = "std")]
+fn read_usize_le(buf: &[u8], pos: usize) -> usize {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&buf[pos..pos + 8]);
+    usize::from_le_bytes(b)
+}
+
+/// parses `buf` as a sequence of current-format records (`crc | timestamp |
+/// store_id | key_size | val_size | key | val`), returning the decoded
+/// `(timestamp, store_id, key, val)` tuples if every record's crc checks out
+/// and the records account for every byte of `buf` exactly — used by
+/// [`upgrade`] to tell a file that only lost its file header apart from an
+/// unrelated, older record layout
+#[cfg(feature = "std")]
+type CurrentFormatRecord = (u64, u64, Vec<u8>, Vec<u8>);
+
+#[cfg(feature = "std")]
+fn parse_current_format_records(buf: &[u8]) -> Option<Vec<CurrentFormatRecord>> {
+    let mut records = Vec::new();
+    let mut pos = 0usize;
+    while pos < buf.len() {
+        if buf.len() - pos < RECORD_HEADER_SIZE {
+            return None;
+        }
+        let stored_crc = u32::from_le_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]]);
+        let body_start = pos + 4;
+        let timestamp = read_u64_le(buf, body_start);
+        let store_id = read_u64_le(buf, body_start + 8);
+        let key_size = read_usize_le(buf, body_start + 16);
+        let val_size = read_usize_le(buf, body_start + 24);
+        let key_start = body_start + 32;
+        let key_end = key_start.checked_add(key_size)?;
+        let val_end = key_end.checked_add(val_size)?;
+        if val_end > buf.len() {
+            return None;
+        }
+        if crc32(&buf[body_start..val_end]) != stored_crc {
+            return None;
+        }
+        records.push((
+            timestamp,
+            store_id,
+            buf[key_start..key_end].to_vec(),
+            buf[key_end..val_end].to_vec(),
+        ));
+        pos = val_end;
+    }
+    Some(records)
+}
 
-pub struct Kvdb {
-    local_mem: HashMap<Vec<u8>, usize>,
+/// parses `buf` as a sequence of baseline, pre-header records (`key_size |
+/// val_size | key | val`, no crc/timestamp/store_id) — the layout every
+/// `./data` file predating this crate's crc and multi-store support was
+/// written in — returning the decoded `(key, val)` pairs if the records
+/// account for every byte of `buf` exactly
+#[cfg(feature = "std")]
+fn parse_legacy_format_records(buf: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+    const LEGACY_HEADER_SIZE: usize = 16;
+    let mut records = Vec::new();
+    let mut pos = 0usize;
+    while pos < buf.len() {
+        if buf.len() - pos < LEGACY_HEADER_SIZE {
+            return None;
+        }
+        let key_size = read_usize_le(buf, pos);
+        let val_size = read_usize_le(buf, pos + 8);
+        let key_start = pos + LEGACY_HEADER_SIZE;
+        let key_end = key_start.checked_add(key_size)?;
+        let val_end = key_end.checked_add(val_size)?;
+        if val_end > buf.len() {
+            return None;
+        }
+        records.push((buf[key_start..key_end].to_vec(), buf[key_end..val_end].to_vec()));
+        pos = val_end;
+    }
+    Some(records)
+}
+
+///
+/// a handle to one of a [`Kvdb`]'s independent keyspaces
+///
+/// obtained via [`Kvdb::open_store`] or [`Kvdb::default_store`] and passed to
+/// `insert`/`get`/`delete`/`keys`/`values`/`iter` to scope them to that
+/// keyspace. the name->id mapping is itself persisted to the backend (as a
+/// reserved record, invisible to callers), so a store's id is stable across
+/// reloads and independent of the order `open_store` is called in
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Store {
+    id: u64,
+}
+
+///
+/// an append-only key/value log, generic over any backend that implements
+/// `Read + Write + Seek`
+///
+/// on `std` targets this is typically backed by `std::fs::File` (see
+/// [`Kvdb::load`]); on `no_std` targets (embedded flash, in-memory buffers)
+/// it can be backed by anything implementing this crate's vendored
+/// `Read + Write + Seek` shims (see the `io_shim` module), via
+/// [`Kvdb::from_storage`]
+///
+pub struct Kvdb<S: Read + Write + Seek> {
+    stores: HashMap<u64, HashMap<Vec<u8>, usize>>,
+    store_ids: HashMap<String, u64>,
+    next_store_id: u64,
     current_pos: usize,
-    reader: Option<BufReader<fs::File>>,
-    writer: Option<BufWriter<fs::File>>,
-    f: Option<fs::File>,
+    /// offset the initial record scan starts at; 0 for [`Kvdb::from_storage`]
+    /// backends, or past the file header for files opened via [`Kvdb::load`]
+    data_start: usize,
+    storage: Option<S>,
+    #[cfg(feature = "std")]
+    path: Option<PathBuf>,
+}
+
+impl<S: Read + Write + Seek> Default for Kvdb<S> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl Kvdb {
+impl<S: Read + Write + Seek> Kvdb<S> {
     pub fn new() -> Self {
+        let mut stores = HashMap::default();
+        stores.insert(DEFAULT_STORE_ID, HashMap::default());
+        let mut store_ids = HashMap::default();
+        store_ids.insert("default".to_string(), DEFAULT_STORE_ID);
         Self {
-            local_mem: HashMap::default(),
-            reader: None,
-            writer: None,
+            stores,
+            store_ids,
+            next_store_id: DEFAULT_STORE_ID + 1,
             current_pos: 0,
-            f: None,
+            data_start: 0,
+            storage: None,
+            #[cfg(feature = "std")]
+            path: None,
         }
     }
 
     ///
-    /// will create/load a key value pair data storage
+    /// loads an already-open backend that implements `Read + Write + Seek`
+    /// without touching the filesystem, e.g. an in-memory buffer on a
+    /// `no_std` target
     ///
-    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
-        let handler = OpenOptions::new()
-            .write(true)
-            .read(true)
-            .create(true)
-            .open(path)?;
-        let write_handler = handler.try_clone()?;
-        let read_handler = handler.try_clone()?;
-        self.f = Some(handler);
-        self.reader = Some(BufReader::new(read_handler));
-        self.writer = Some(BufWriter::new(write_handler));
-        self.load_into_hashmap()?;
-        Ok(())
+    pub fn from_storage(storage: S) -> Result<Self> {
+        let mut kv = Self::new();
+        kv.storage = Some(storage);
+        kv.load_into_hashmap()?;
+        Ok(kv)
+    }
+
+    ///
+    /// the implicit keyspace used by callers that don't need multiple stores
+    ///
+    pub fn default_store(&self) -> Store {
+        Store {
+            id: DEFAULT_STORE_ID,
+        }
+    }
+
+    ///
+    /// opens (creating if necessary) a named, independent keyspace within
+    /// this `Kvdb`
+    ///
+    /// records written through one store's handle are invisible to another
+    /// store's `get`/`keys`/`iter`, even though every store shares the same
+    /// underlying backend. the first time a name is opened, its id is
+    /// persisted to the backend via a reserved registry record, so later
+    /// calls (in this session, or after a fresh `load`) resolve the same
+    /// name back to the same id regardless of call order. must be called
+    /// after `load`/`from_storage` has populated `storage`
+    ///
+    pub fn open_store<N: Into<String>>(&mut self, name: N) -> Result<Store> {
+        let name = name.into();
+        if let Some(id) = self.store_ids.get(&name) {
+            return Ok(Store { id: *id });
+        }
+        let id = self.next_store_id;
+        self.next_store_id += 1;
+        let registry = Store {
+            id: STORE_REGISTRY_ID,
+        };
+        self.insert_by_key_ref(&registry, name.as_bytes(), &id.to_le_bytes())?;
+        self.store_ids.insert(name, id);
+        self.stores.entry(id).or_default();
+        Ok(Store { id })
     }
 
     fn load_into_hashmap(&mut self) -> Result<()> {
-        let reader = self.reader.as_mut().expect("reader is empty");
-        let mut position = 0;
+        let mut position = self.data_start;
+        let storage = self.storage.as_mut().expect("storage is empty");
         loop {
-            let mut key_size_buff: [u8; 8] = [0; 8];
-            match reader.read_exact(&mut key_size_buff) {
+            let mut crc_buff: [u8; 4] = [0; 4];
+            match storage.read_exact(&mut crc_buff) {
                 Ok(()) => {
-                    let key_size = usize::from_le_bytes(key_size_buff);
-                    reader.read_exact(&mut key_size_buff)?;
-                    let value_size = usize::from_le_bytes(key_size_buff);
+                    // a crash can tear an append anywhere inside a record,
+                    // not just leave a full-length record with a bad crc, so
+                    // any of these reads hitting eof mid-record is treated
+                    // the same as the crc mismatch below: stop scanning here
+                    // rather than erroring `load` out entirely
+                    macro_rules! read_or_truncate {
+                        ($buf:expr) => {
+                            match storage.read_exact($buf) {
+                                Ok(()) => {}
+                                Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => {
+                                    storage.seek(SeekFrom::Start(position as u64))?;
+                                    self.current_pos = position;
+                                    break;
+                                }
+                                Err(err) => return Err(err),
+                            }
+                        };
+                    }
+
+                    let stored_crc = u32::from_le_bytes(crc_buff);
+                    let mut timestamp_buff: [u8; 8] = [0; 8];
+                    read_or_truncate!(&mut timestamp_buff);
+                    let mut store_id_buff: [u8; 8] = [0; 8];
+                    read_or_truncate!(&mut store_id_buff);
+                    let store_id = u64::from_le_bytes(store_id_buff);
+                    let mut size_buff: [u8; 8] = [0; 8];
+                    read_or_truncate!(&mut size_buff);
+                    let key_size = usize::from_le_bytes(size_buff);
+                    read_or_truncate!(&mut size_buff);
+                    let value_size = usize::from_le_bytes(size_buff);
                     let mut vec_key = vec![0u8; key_size];
-                    reader.read(&mut vec_key)?;
-                    reader.seek(SeekFrom::Current((value_size) as i64))?;
-                    self.local_mem.insert(vec_key, position);
-                    position += std::mem::size_of::<usize>() * 2 + value_size + key_size;
+                    read_or_truncate!(&mut vec_key);
+                    let mut vec_val = vec![0u8; value_size];
+                    read_or_truncate!(&mut vec_val);
+
+                    let mut body =
+                        Vec::with_capacity(8 + 8 + 8 + key_size + value_size);
+                    body.extend_from_slice(&timestamp_buff);
+                    body.extend_from_slice(&store_id_buff);
+                    body.extend_from_slice(&usize::to_le_bytes(key_size));
+                    body.extend_from_slice(&usize::to_le_bytes(value_size));
+                    body.extend_from_slice(&vec_key);
+                    body.extend_from_slice(&vec_val);
+
+                    if crc32(&body) != stored_crc {
+                        // torn/corrupt write: treat this record as the end of
+                        // valid data rather than indexing garbage
+                        storage.seek(SeekFrom::Start(position as u64))?;
+                        self.current_pos = position;
+                        break;
+                    }
+
+                    if store_id == STORE_REGISTRY_ID && value_size == 8 {
+                        // reserved registry record: rebuild the name->id map
+                        // and the id counter instead of exposing it as data
+                        let mut id_buff = [0u8; 8];
+                        id_buff.copy_from_slice(&vec_val);
+                        let registered_id = u64::from_le_bytes(id_buff);
+                        if let Ok(name) = String::from_utf8(vec_key.clone()) {
+                            self.store_ids.insert(name, registered_id);
+                        }
+                        if registered_id >= self.next_store_id {
+                            self.next_store_id = registered_id + 1;
+                        }
+                        self.stores.entry(registered_id).or_default();
+                    }
+
+                    self.stores
+                        .entry(store_id)
+                        .or_default()
+                        .insert(vec_key, position);
+                    position += RECORD_HEADER_SIZE + value_size + key_size;
                 }
                 Err(err) => match err.kind() {
                     ErrorKind::UnexpectedEof => {
-                        reader.seek(SeekFrom::Start(0))?;
+                        storage.seek(SeekFrom::Start(0))?;
                         self.current_pos = position;
                         break;
                     }
@@ -73,25 +494,48 @@ impl Kvdb {
         Ok(())
     }
 
-    fn get_by_key_ref<V: From<Vec<u8>>>(&mut self, key: &Vec<u8>) -> Result<V> {
-        let reader = self.reader.as_mut().unwrap();
-        let pos = self.local_mem.get(key);
+    fn get_by_key_ref<V: From<Vec<u8>>>(&mut self, store: &Store, key: &Vec<u8>) -> Result<V> {
+        let pos = self
+            .stores
+            .get(&store.id)
+            .and_then(|local_mem| local_mem.get(key))
+            .copied();
+        let storage = self.storage.as_mut().unwrap();
         if let Some(pos) = pos {
             // put pointer into read position
-            reader.seek(SeekFrom::Start(*pos as u64))?;
+            storage.seek(SeekFrom::Start(pos as u64))?;
+            let mut crc_buff: [u8; 4] = [0; 4];
+            storage.read_exact(&mut crc_buff)?;
+            let stored_crc = u32::from_le_bytes(crc_buff);
+            let mut timestamp_buff: [u8; 8] = [0; 8];
+            storage.read_exact(&mut timestamp_buff)?;
+            let mut store_id_buff: [u8; 8] = [0; 8];
+            storage.read_exact(&mut store_id_buff)?;
             let mut size_buff: [u8; 8] = [0; 8];
             // retrieve key size for the future
-            reader.read_exact(&mut size_buff)?;
+            storage.read_exact(&mut size_buff)?;
             let key_size = usize::from_le_bytes(size_buff);
             // retrieve val size
-            reader.read_exact(&mut size_buff)?;
+            storage.read_exact(&mut size_buff)?;
             let val_size = usize::from_le_bytes(size_buff);
             if val_size != 0 {
                 let mut vec_key = vec![0u8; key_size];
-                reader.read(&mut vec_key)?;
+                storage.read_exact(&mut vec_key)?;
                 // Read val
                 let mut vec = vec![0u8; val_size];
-                reader.read(&mut vec)?;
+                storage.read_exact(&mut vec)?;
+
+                let mut body = Vec::with_capacity(8 + 8 + 8 + key_size + val_size);
+                body.extend_from_slice(&timestamp_buff);
+                body.extend_from_slice(&store_id_buff);
+                body.extend_from_slice(&usize::to_le_bytes(key_size));
+                body.extend_from_slice(&usize::to_le_bytes(val_size));
+                body.extend_from_slice(&vec_key);
+                body.extend_from_slice(&vec);
+                if crc32(&body) != stored_crc {
+                    return Result::Err(Error::from(ErrorKind::InvalidData));
+                }
+
                 return Ok(V::from(vec));
             }
         }
@@ -99,15 +543,16 @@ impl Kvdb {
     }
 
     ///
-    /// retrieves a key value pair, will return an error if it doesn't exist
+    /// retrieves a key value pair from `store`, will return an error if it
+    /// doesn't exist
     ///
-    pub fn get<T: Into<Vec<u8>>, V: From<Vec<u8>>>(&mut self, key: T) -> Result<V> {
+    pub fn get<T: Into<Vec<u8>>, V: From<Vec<u8>>>(&mut self, store: &Store, key: T) -> Result<V> {
         let k_buff = key.into();
-        return self.get_by_key_ref(&k_buff);
+        self.get_by_key_ref(store, &k_buff)
     }
 
     ///
-    /// deletes a key value pair
+    /// deletes a key value pair from `store`
     ///
     /// it won't delete it in the file, but will insert
     /// the key again but with the 0 value, so the
@@ -115,11 +560,12 @@ impl Kvdb {
     ///
     pub fn delete<T: Into<Vec<u8>>, V: Into<Vec<u8>> + From<Vec<u8>>>(
         &mut self,
+        store: &Store,
         key: T,
     ) -> Result<V> {
         let buff = key.into();
         // We check that it indeed exists
-        let result = self.get_by_key_ref::<V>(&buff);
+        let result = self.get_by_key_ref::<V>(store, &buff);
         if let Err(err) = result {
             return Err(err);
         }
@@ -128,7 +574,7 @@ impl Kvdb {
         // empty vector to insert into the file
         let empty = Vec::with_capacity(0);
         // override previous value
-        self.insert_by_key_ref(&buff, &empty)?;
+        self.insert_by_key_ref(store, &buff, &empty)?;
         Ok(v)
     }
 
@@ -136,34 +582,461 @@ impl Kvdb {
     /// insert method used by `delete` and `insert`
     /// - it is used by delete because if you pass an empty vector to the value, it will basically
     ///   delete the key
-    /// useful because it borrows the key and value
+    ///   useful because it borrows the key and value
     ///
-    fn insert_by_key_ref(&mut self, key: &Vec<u8>, val: &Vec<u8>) -> Result<()> {
-        let writer = self.writer.as_mut().unwrap();
+    fn insert_by_key_ref(&mut self, store: &Store, key: &[u8], val: &[u8]) -> Result<()> {
+        let timestamp = now_millis();
+        let key_len = key.len();
+        let val_len = val.len();
+
+        // crc is computed over everything after the crc field itself
+        let mut body = Vec::with_capacity(8 + 8 + 8 + key_len + val_len);
+        body.extend_from_slice(&u64::to_le_bytes(timestamp));
+        body.extend_from_slice(&u64::to_le_bytes(store.id));
+        body.extend_from_slice(&usize::to_le_bytes(key_len));
+        body.extend_from_slice(&usize::to_le_bytes(val_len));
+        body.extend_from_slice(key);
+        body.extend_from_slice(val);
+        let crc = crc32(&body);
+
+        let storage = self.storage.as_mut().unwrap();
         // just in case, go to the end of the file
-        writer.seek(SeekFrom::End(0))?;
-        let len = key.len();
+        // (the end of valid data: a torn tail dropped by the load scan is
+        // overwritten rather than appended after)
+        storage.seek(SeekFrom::Start(self.current_pos as u64))?;
         let initial_pos = self.current_pos;
-        self.current_pos += writer.write(&usize::to_le_bytes(len))?;
-        self.current_pos += writer.write(&usize::to_le_bytes(val.len()))?;
-        self.current_pos += writer.write(key)?;
-        self.current_pos += writer.write(val)?;
-        self.local_mem.insert(key.clone(), initial_pos);
-        writer.flush()?;
+        self.current_pos += storage.write(&u32::to_le_bytes(crc))?;
+        self.current_pos += storage.write(&body)?;
+        self.stores
+            .entry(store.id)
+            .or_default()
+            .insert(key.to_vec(), initial_pos);
+        storage.flush()?;
         Ok(())
     }
 
     ///
-    /// inserts a key value pair into the file
+    /// inserts a key value pair into `store`
     ///
-    pub fn insert<T: Into<Vec<u8>>, V: Into<Vec<u8>>>(&mut self, key: T, val: V) -> Result<()> {
+    pub fn insert<T: Into<Vec<u8>>, V: Into<Vec<u8>>>(
+        &mut self,
+        store: &Store,
+        key: T,
+        val: V,
+    ) -> Result<()> {
         let key = key.into();
         let val = val.into();
-        self.insert_by_key_ref(&key, &val)
+        self.insert_by_key_ref(store, &key, &val)
+    }
+
+    /// snapshots `store`'s live (non-tombstone) entries as `(key, position)` pairs
+    ///
+    /// this is a point-in-time copy of the store's position map, filtered by
+    /// the stored val_size, so later `insert`/`delete` calls don't affect an
+    /// iterator already in flight
+    fn live_entries(&mut self, store: &Store) -> Result<Vec<(Vec<u8>, usize)>> {
+        let candidates: Vec<(Vec<u8>, usize)> = self
+            .stores
+            .get(&store.id)
+            .map(|local_mem| local_mem.iter().map(|(k, v)| (k.clone(), *v)).collect())
+            .unwrap_or_default();
+        let storage = self.storage.as_mut().expect("storage is empty");
+        let mut live = Vec::with_capacity(candidates.len());
+        for (key, pos) in candidates {
+            storage.seek(SeekFrom::Start(pos as u64))?;
+            // skip crc + timestamp + store_id + key_size to reach val_size
+            storage.seek(SeekFrom::Current(
+                (core::mem::size_of::<u32>()
+                    + core::mem::size_of::<u64>() * 2
+                    + core::mem::size_of::<usize>()) as i64,
+            ))?;
+            let mut size_buff: [u8; 8] = [0; 8];
+            storage.read_exact(&mut size_buff)?;
+            let val_size = usize::from_le_bytes(size_buff);
+            if val_size != 0 {
+                live.push((key, pos));
+            }
+        }
+        Ok(live)
+    }
+
+    /// reads the value stored at `pos`, assuming it points at a live record
+    fn read_value_at<V: From<Vec<u8>>>(&mut self, pos: usize) -> Result<V> {
+        let storage = self.storage.as_mut().expect("storage is empty");
+        storage.seek(SeekFrom::Start(pos as u64))?;
+        // skip crc + timestamp + store_id to reach key_size
+        storage.seek(SeekFrom::Current(
+            (core::mem::size_of::<u32>() + core::mem::size_of::<u64>() * 2) as i64,
+        ))?;
+        let mut size_buff: [u8; 8] = [0; 8];
+        storage.read_exact(&mut size_buff)?;
+        let key_size = usize::from_le_bytes(size_buff);
+        storage.read_exact(&mut size_buff)?;
+        let val_size = usize::from_le_bytes(size_buff);
+        storage.seek(SeekFrom::Current(key_size as i64))?;
+        let mut vec = vec![0u8; val_size];
+        storage.read_exact(&mut vec)?;
+        Ok(V::from(vec))
+    }
+
+    ///
+    /// returns an iterator over the live keys currently in `store`
+    ///
+    /// tombstones (deleted keys) are skipped. the snapshot is taken at call
+    /// time, so it does not observe inserts/deletes made after this call.
+    /// iteration order follows the store's internal `HashMap` order, which
+    /// is unspecified (not insertion order)
+    ///
+    pub fn keys(&mut self, store: &Store) -> Result<Keys> {
+        let entries = self.live_entries(store)?;
+        Ok(Keys {
+            entries: entries.into_iter(),
+        })
+    }
+
+    ///
+    /// returns an iterator over the live values currently in `store`,
+    /// reading each one lazily by seeking to its stored offset
+    ///
+    /// see [`Kvdb::keys`] for the ordering and snapshot semantics
+    ///
+    pub fn values<V: From<Vec<u8>>>(&mut self, store: &Store) -> Result<Values<'_, S, V>> {
+        let entries = self.live_entries(store)?;
+        Ok(Values {
+            kv: self,
+            entries: entries.into_iter(),
+            _marker: PhantomData,
+        })
+    }
+
+    ///
+    /// returns an iterator over the live `(key, value)` pairs currently in
+    /// `store`, reading each value lazily by seeking to its stored offset
+    ///
+    /// see [`Kvdb::keys`] for the ordering and snapshot semantics
+    ///
+    pub fn iter<V: From<Vec<u8>>>(&mut self, store: &Store) -> Result<Iter<'_, S, V>> {
+        let entries = self.live_entries(store)?;
+        Ok(Iter {
+            kv: self,
+            entries: entries.into_iter(),
+            _marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl Kvdb<fs::File> {
+    ///
+    /// will create/load a key value pair data storage backed by a file on
+    /// disk
+    ///
+    /// freshly created files are stamped with the current [`FILE_MAGIC`]/
+    /// [`FORMAT_VERSION`] header; existing files are expected to already
+    /// carry one. files written before file headers existed are rejected
+    /// with `ErrorKind::InvalidData` — run [`upgrade`] on them first. files
+    /// whose header carries a different format version are rejected with
+    /// `ErrorKind::InvalidData` as well; [`upgrade`] does not migrate those
+    ///
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let mut handler = OpenOptions::new()
+            .write(true)
+            .read(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        self.path = Some(path.as_ref().to_path_buf());
+
+        if handler.metadata()?.len() == 0 {
+            // freshly created: stamp the current format header
+            handler.write_all(&file_header_bytes())?;
+            handler.flush()?;
+        } else {
+            let mut header_buff = [0u8; FILE_HEADER_SIZE];
+            handler.read_exact(&mut header_buff).map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    "missing or truncated kvdb file header; run kvdb::upgrade on this file first",
+                )
+            })?;
+            let mut magic = [0u8; 4];
+            magic.copy_from_slice(&header_buff[0..4]);
+            if magic != FILE_MAGIC {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "not a kvdb file, or it predates file headers; run kvdb::upgrade on this file first",
+                ));
+            }
+            let version = u16::from_le_bytes([header_buff[4], header_buff[5]]);
+            if version != FORMAT_VERSION {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "kvdb file format version is not supported by this build",
+                ));
+            }
+        }
+
+        self.data_start = FILE_HEADER_SIZE;
+        self.storage = Some(handler);
+        self.load_into_hashmap()
+    }
+
+    ///
+    /// reclaims dead space left behind by overwrites and tombstones, across
+    /// every store hosted by this `Kvdb`
+    ///
+    /// writes every live record (as pointed at by each store's position map)
+    /// into a fresh temp file, skipping tombstones (records whose stored
+    /// value size is 0), then `fs::rename`s the temp file over the original
+    /// once it has been flushed successfully. a crash before the rename
+    /// leaves the original file untouched
+    ///
+    pub fn compact(&mut self) -> Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .expect("load must be called before compact")
+            .clone();
+        let tmp_path = path.with_extension("compact.tmp");
+        let tmp_file = OpenOptions::new()
+            .write(true)
+            .read(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        let mut tmp_writer = BufWriter::new(tmp_file);
+        tmp_writer.write_all(&file_header_bytes())?;
+
+        // snapshot every store's live entries up front, tagged with their
+        // store id, so we don't hold a borrow of `stores` while
+        // seeking/reading through `storage`
+        let mut entries: Vec<(u64, Vec<u8>, usize)> = self
+            .stores
+            .iter()
+            .flat_map(|(store_id, local_mem)| {
+                local_mem
+                    .iter()
+                    .map(move |(k, v)| (*store_id, k.clone(), *v))
+            })
+            .collect();
+        entries.sort_by_key(|(_, _, pos)| *pos);
+
+        let mut new_stores: HashMap<u64, HashMap<Vec<u8>, usize>> = self
+            .stores
+            .keys()
+            .map(|id| (*id, HashMap::default()))
+            .collect();
+        let mut new_pos = self.data_start;
+        let storage = self.storage.as_mut().expect("storage is empty");
+        for (store_id, key, pos) in entries {
+            storage.seek(SeekFrom::Start(pos as u64))?;
+            let mut crc_buff: [u8; 4] = [0; 4];
+            storage.read_exact(&mut crc_buff)?;
+            let mut timestamp_buff: [u8; 8] = [0; 8];
+            storage.read_exact(&mut timestamp_buff)?;
+            let mut store_id_buff: [u8; 8] = [0; 8];
+            storage.read_exact(&mut store_id_buff)?;
+            let mut size_buff: [u8; 8] = [0; 8];
+            storage.read_exact(&mut size_buff)?;
+            let key_size = usize::from_le_bytes(size_buff);
+            storage.read_exact(&mut size_buff)?;
+            let val_size = usize::from_le_bytes(size_buff);
+            let mut vec_key = vec![0u8; key_size];
+            storage.read_exact(&mut vec_key)?;
+            if val_size == 0 {
+                // tombstone: dead, drop it during the merge
+                continue;
+            }
+            let mut vec_val = vec![0u8; val_size];
+            storage.read_exact(&mut vec_val)?;
+
+            // the record's bytes (and thus its crc) are unchanged by the
+            // merge, only its offset moves, so copy the header verbatim
+            tmp_writer.write_all(&crc_buff)?;
+            tmp_writer.write_all(&timestamp_buff)?;
+            tmp_writer.write_all(&store_id_buff)?;
+            tmp_writer.write_all(&usize::to_le_bytes(key_size))?;
+            tmp_writer.write_all(&usize::to_le_bytes(val_size))?;
+            tmp_writer.write_all(&vec_key)?;
+            tmp_writer.write_all(&vec_val)?;
+            new_stores.entry(store_id).or_default().insert(key, new_pos);
+            new_pos += RECORD_HEADER_SIZE + key_size + val_size;
+        }
+        tmp_writer.flush()?;
+        drop(tmp_writer);
+
+        // only swap the file in once the new one is known-good on disk
+        fs::rename(&tmp_path, &path)?;
+
+        let handler = OpenOptions::new().write(true).read(true).open(&path)?;
+        self.storage = Some(handler);
+        self.stores = new_stores;
+        self.current_pos = new_pos;
+        Ok(())
     }
 }
 
-#[cfg(test)]
+///
+/// rewrites `path` in place so it carries the current [`FILE_MAGIC`]/
+/// [`FORMAT_VERSION`] file header and current per-record layout, so that
+/// [`Kvdb::load`] accepts it
+///
+/// a file with no recognized header is either:
+/// - a file already written in the *current* record layout (`crc |
+///   timestamp | store_id | key_size | val_size | key | val`) that merely
+///   lost its file header — the current header is prepended and the records
+///   are copied through unchanged, or
+/// - a genuinely pre-series baseline file (`key_size | val_size | key |
+///   val`, no crc/timestamp/store_id at all — the layout every `./data`
+///   file predating this crate's crc and multi-store support was written
+///   in) — every record is re-encoded into the current layout, with a
+///   freshly computed crc/timestamp and [`DEFAULT_STORE_ID`]
+///
+/// a file that doesn't cleanly parse as either is rejected with
+/// `ErrorKind::InvalidData` rather than silently dropping its records. a
+/// file that already carries a recognized header is left untouched — there
+/// is, so far, only one current format version. mirrors the intent of
+/// Skytable's `upgrade` compatibility routine: run this once against an old
+/// file before calling [`Kvdb::load`] on it
+///
+#[cfg(feature = "std")]
+pub fn upgrade<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+
+    let mut header_buff = [0u8; FILE_HEADER_SIZE];
+    let has_recognized_header = match file.read_exact(&mut header_buff) {
+        Ok(()) => {
+            let mut magic = [0u8; 4];
+            magic.copy_from_slice(&header_buff[0..4]);
+            if magic == FILE_MAGIC {
+                let version = u16::from_le_bytes([header_buff[4], header_buff[5]]);
+                if version != FORMAT_VERSION {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "kvdb::upgrade: file has a newer or unrecognized format version; \
+                         this build of kvdb does not know how to migrate it",
+                    ));
+                }
+                true
+            } else {
+                false
+            }
+        }
+        Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => false,
+        Err(err) => return Err(err),
+    };
+    if has_recognized_header {
+        return Ok(());
+    }
+
+    file.seek(SeekFrom::Start(0))?;
+    let mut body = Vec::new();
+    file.read_to_end(&mut body)?;
+
+    let tmp_path = path.with_extension("upgrade.tmp");
+    let mut tmp = OpenOptions::new()
+        .write(true)
+        .read(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp_path)?;
+    tmp.write_all(&file_header_bytes())?;
+
+    if parse_current_format_records(&body).is_some() {
+        // already in the current per-record layout; only the file header
+        // was missing, so copy the records through verbatim
+        tmp.write_all(&body)?;
+    } else if let Some(records) = parse_legacy_format_records(&body) {
+        // genuinely pre-series baseline layout: re-encode every record into
+        // the current one, since the crc/timestamp/store_id fields never
+        // existed on disk to copy through
+        for (key, val) in records {
+            let timestamp = now_millis();
+            let mut record_body =
+                Vec::with_capacity(8 + 8 + 8 + 8 + key.len() + val.len());
+            record_body.extend_from_slice(&u64::to_le_bytes(timestamp));
+            record_body.extend_from_slice(&u64::to_le_bytes(DEFAULT_STORE_ID));
+            record_body.extend_from_slice(&usize::to_le_bytes(key.len()));
+            record_body.extend_from_slice(&usize::to_le_bytes(val.len()));
+            record_body.extend_from_slice(&key);
+            record_body.extend_from_slice(&val);
+            tmp.write_all(&u32::to_le_bytes(crc32(&record_body)))?;
+            tmp.write_all(&record_body)?;
+        }
+    } else {
+        drop(tmp);
+        let _ = fs::remove_file(&tmp_path);
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "kvdb::upgrade: file matches neither the current nor the legacy \
+             kvdb record layout; refusing to guess and silently drop data",
+        ));
+    }
+
+    tmp.flush()?;
+    drop(tmp);
+
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+///
+/// iterator over the live keys of a [`Kvdb`] store, returned by [`Kvdb::keys`]
+///
+pub struct Keys {
+    entries: IntoIter<(Vec<u8>, usize)>,
+}
+
+impl Iterator for Keys {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|(key, _)| key)
+    }
+}
+
+///
+/// iterator over the live values of a [`Kvdb`] store, returned by [`Kvdb::values`]
+///
+pub struct Values<'a, S: Read + Write + Seek, V> {
+    kv: &'a mut Kvdb<S>,
+    entries: IntoIter<(Vec<u8>, usize)>,
+    _marker: PhantomData<V>,
+}
+
+impl<'a, S: Read + Write + Seek, V: From<Vec<u8>>> Iterator for Values<'a, S, V> {
+    type Item = Result<V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, pos) = self.entries.next()?;
+        Some(self.kv.read_value_at(pos))
+    }
+}
+
+///
+/// iterator over the live `(key, value)` pairs of a [`Kvdb`] store, returned
+/// by [`Kvdb::iter`]
+///
+pub struct Iter<'a, S: Read + Write + Seek, V> {
+    kv: &'a mut Kvdb<S>,
+    entries: IntoIter<(Vec<u8>, usize)>,
+    _marker: PhantomData<V>,
+}
+
+impl<'a, S: Read + Write + Seek, V: From<Vec<u8>>> Iterator for Iter<'a, S, V> {
+    type Item = Result<(Vec<u8>, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, pos) = self.entries.next()?;
+        match self.kv.read_value_at(pos) {
+            Ok(val) => Some(Ok((key, val))),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     #[derive(Debug)]
     struct TestValue {
@@ -178,42 +1051,46 @@ mod tests {
         }
     }
 
-    impl Into<Vec<u8>> for TestValue {
-        fn into(self) -> Vec<u8> {
-            self.value.into_bytes()
+    impl From<TestValue> for Vec<u8> {
+        fn from(val: TestValue) -> Self {
+            val.value.into_bytes()
         }
     }
 
     use super::Kvdb;
+    use std::fs;
     fn pure_inserting_works() {
-        let mut kv = Kvdb::new();
+        let mut kv: Kvdb<fs::File> = Kvdb::new();
         kv.load("./data").expect("expect load to work");
+        let store = kv.default_store();
         for i in 0..100 {
-            let string = String::from(format!("test{}", i));
+            let string = format!("test{}", i);
             let value: TestValue = TestValue {
                 value: string.clone(),
             };
-            kv.insert(format!("key_test{}", i), value)
+            kv.insert(&store, format!("key_test{}", i), value)
                 .expect("expect this to work!");
-            let val: TestValue = kv.get(format!("key_test{}", i)).unwrap();
+            let val: TestValue = kv.get(&store, format!("key_test{}", i)).unwrap();
             assert_eq!(string, val.value);
         }
     }
     #[test]
     fn load_works() {
         pure_inserting_works();
-        let mut kv = Kvdb::new();
+        let mut kv: Kvdb<fs::File> = Kvdb::new();
         if let Err(err) = kv.load("./data") {
             println!("error: {:?}", err);
             return;
         }
+        let store = kv.default_store();
         assert_eq!(
-            kv.delete::<_, TestValue>("key_test0").unwrap().value,
+            kv.delete::<_, TestValue>(&store, "key_test0").unwrap().value,
             "test0"
         );
-        let shouldnt_exist = kv.get::<_, TestValue>("key_test0");
+        let shouldnt_exist = kv.get::<_, TestValue>(&store, "key_test0");
         matches!(shouldnt_exist, Err(_err));
         kv.insert(
+            &store,
             "key_test0",
             TestValue {
                 value: "test0".to_string(),
@@ -221,8 +1098,304 @@ mod tests {
         )
         .expect("expect the insert to work");
         for i in 0..100 {
-            let val: TestValue = kv.get(format!("key_test{}", i)).unwrap();
+            let val: TestValue = kv.get(&store, format!("key_test{}", i)).unwrap();
             assert_eq!(val.value, format!("test{}", i));
         }
     }
+
+    #[test]
+    fn iter_skips_tombstones() {
+        let mut kv: Kvdb<fs::File> = Kvdb::new();
+        kv.load("./data_iter").expect("expect load to work");
+        let store = kv.default_store();
+        for i in 0..10 {
+            kv.insert(
+                &store,
+                format!("iter_key{}", i),
+                TestValue {
+                    value: format!("iter_val{}", i),
+                },
+            )
+            .expect("expect this to work!");
+        }
+        kv.delete::<_, TestValue>(&store, "iter_key0")
+            .expect("expect delete to work");
+
+        let keys: Vec<Vec<u8>> = kv.keys(&store).expect("expect keys to work").collect();
+        assert_eq!(keys.len(), 9);
+        assert!(!keys.contains(&b"iter_key0".to_vec()));
+
+        let values: Vec<String> = kv
+            .values::<TestValue>(&store)
+            .expect("expect values to work")
+            .map(|v| v.unwrap().value)
+            .collect();
+        assert_eq!(values.len(), 9);
+
+        let pairs: Vec<(Vec<u8>, TestValue)> = kv
+            .iter::<TestValue>(&store)
+            .expect("expect iter to work")
+            .map(|pair| pair.unwrap())
+            .collect();
+        assert_eq!(pairs.len(), 9);
+    }
+
+    #[test]
+    fn compact_reclaims_dead_space() {
+        let path = "./data_compact";
+        let mut kv: Kvdb<fs::File> = Kvdb::new();
+        kv.load(path).expect("expect load to work");
+        let store = kv.default_store();
+
+        for i in 0..20 {
+            kv.insert(
+                &store,
+                format!("compact_key{}", i),
+                TestValue {
+                    value: format!("v{}", i),
+                },
+            )
+            .expect("expect this to work!");
+        }
+        // overwrite every key once (leaving the old copies dead) and
+        // tombstone half of them, so compaction has both kinds of dead
+        // space to reclaim
+        for i in 0..20 {
+            kv.insert(
+                &store,
+                format!("compact_key{}", i),
+                TestValue {
+                    value: format!("v{}-overwritten", i),
+                },
+            )
+            .expect("expect this to work!");
+        }
+        for i in 0..10 {
+            kv.delete::<_, TestValue>(&store, format!("compact_key{}", i))
+                .expect("expect delete to work");
+        }
+
+        let size_before = fs::metadata(path).unwrap().len();
+        kv.compact().expect("expect compact to work");
+        let size_after = fs::metadata(path).unwrap().len();
+        assert!(
+            size_after < size_before,
+            "compact should shrink the file: {} -> {}",
+            size_before,
+            size_after
+        );
+
+        for i in 0..10 {
+            let shouldnt_exist = kv.get::<_, TestValue>(&store, format!("compact_key{}", i));
+            assert!(shouldnt_exist.is_err());
+        }
+        for i in 10..20 {
+            let val: TestValue = kv.get(&store, format!("compact_key{}", i)).unwrap();
+            assert_eq!(val.value, format!("v{}-overwritten", i));
+        }
+
+        // the compacted file should also reload cleanly from scratch
+        let mut reloaded: Kvdb<fs::File> = Kvdb::new();
+        reloaded.load(path).expect("expect reload after compact to work");
+        let store = reloaded.default_store();
+        for i in 10..20 {
+            let val: TestValue = reloaded.get(&store, format!("compact_key{}", i)).unwrap();
+            assert_eq!(val.value, format!("v{}-overwritten", i));
+        }
+    }
+
+    #[test]
+    fn stores_are_isolated() {
+        let mut kv: Kvdb<fs::File> = Kvdb::new();
+        kv.load("./data_stores").expect("expect load to work");
+        let users = kv.open_store("users").expect("expect open_store to work");
+        let sessions = kv
+            .open_store("sessions")
+            .expect("expect open_store to work");
+
+        kv.insert(
+            &users,
+            "alice",
+            TestValue {
+                value: "admin".to_string(),
+            },
+        )
+        .expect("expect this to work!");
+        kv.insert(
+            &sessions,
+            "alice",
+            TestValue {
+                value: "token-123".to_string(),
+            },
+        )
+        .expect("expect this to work!");
+
+        assert_eq!(
+            kv.get::<_, TestValue>(&users, "alice").unwrap().value,
+            "admin"
+        );
+        assert_eq!(
+            kv.get::<_, TestValue>(&sessions, "alice").unwrap().value,
+            "token-123"
+        );
+
+        let users_keys: Vec<Vec<u8>> = kv.keys(&users).expect("expect keys to work").collect();
+        assert_eq!(users_keys, vec![b"alice".to_vec()]);
+    }
+
+    #[test]
+    fn stores_survive_reload() {
+        let path = "./data_stores_reload";
+        {
+            let mut kv: Kvdb<fs::File> = Kvdb::new();
+            kv.load(path).expect("expect load to work");
+            let users = kv.open_store("users").expect("expect open_store to work");
+            let sessions = kv
+                .open_store("sessions")
+                .expect("expect open_store to work");
+            kv.insert(
+                &users,
+                "alice",
+                TestValue {
+                    value: "admin".to_string(),
+                },
+            )
+            .expect("expect this to work!");
+            kv.insert(
+                &sessions,
+                "alice",
+                TestValue {
+                    value: "token-123".to_string(),
+                },
+            )
+            .expect("expect this to work!");
+        }
+
+        // reopen in the opposite order (mimicking a second process) and make
+        // sure each named store still resolves its own keys by name, rather
+        // than clobbering the just-loaded map or picking up the wrong id
+        let mut kv: Kvdb<fs::File> = Kvdb::new();
+        kv.load(path).expect("expect load to work after reopen");
+        let sessions = kv
+            .open_store("sessions")
+            .expect("expect open_store to work");
+        let users = kv.open_store("users").expect("expect open_store to work");
+        assert_eq!(
+            kv.get::<_, TestValue>(&users, "alice").unwrap().value,
+            "admin"
+        );
+        assert_eq!(
+            kv.get::<_, TestValue>(&sessions, "alice").unwrap().value,
+            "token-123"
+        );
+    }
+
+    #[test]
+    fn load_recovers_from_torn_append() {
+        let path = "./data_torn";
+        let _ = fs::remove_file(path);
+        {
+            let mut kv: Kvdb<fs::File> = Kvdb::new();
+            kv.load(path).expect("expect load to work");
+            let store = kv.default_store();
+            kv.insert(
+                &store,
+                "torn_key0",
+                TestValue {
+                    value: "torn_val0".to_string(),
+                },
+            )
+            .expect("expect insert to work");
+            kv.insert(
+                &store,
+                "torn_key1",
+                TestValue {
+                    value: "torn_val1".to_string(),
+                },
+            )
+            .expect("expect insert to work");
+        }
+        // simulate a crash mid-append: the crc made it to disk but the rest
+        // of the record (timestamp/store_id/sizes/key/val) got cut off
+        {
+            use std::io::Write as _;
+            let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+            file.write_all(&[0xAB, 0xCD, 0xEF, 0x01]).unwrap();
+        }
+
+        let mut kv: Kvdb<fs::File> = Kvdb::new();
+        kv.load(path)
+            .expect("expect load to recover instead of erroring on the torn tail");
+        let store = kv.default_store();
+        let val0: TestValue = kv.get(&store, "torn_key0").unwrap();
+        assert_eq!(val0.value, "torn_val0");
+        let val1: TestValue = kv.get(&store, "torn_key1").unwrap();
+        assert_eq!(val1.value, "torn_val1");
+    }
+
+    #[test]
+    fn upgrade_adds_missing_header() {
+        let path = "./data_legacy";
+        {
+            let mut kv: Kvdb<fs::File> = Kvdb::new();
+            kv.load(path).expect("expect load to work");
+            let store = kv.default_store();
+            kv.insert(
+                &store,
+                "legacy_key",
+                TestValue {
+                    value: "legacy_val".to_string(),
+                },
+            )
+            .expect("expect insert to work");
+        }
+        // simulate a file written before file headers existed, by stripping
+        // the header `load` would normally have stamped
+        let body = fs::read(path).unwrap();
+        fs::write(path, &body[super::FILE_HEADER_SIZE..]).unwrap();
+
+        super::upgrade(path).expect("expect upgrade to work");
+
+        let mut kv: Kvdb<fs::File> = Kvdb::new();
+        kv.load(path).expect("expect load to work after upgrade");
+        let store = kv.default_store();
+        let val: TestValue = kv.get(&store, "legacy_key").unwrap();
+        assert_eq!(val.value, "legacy_val");
+    }
+
+    #[test]
+    fn upgrade_migrates_legacy_record_layout() {
+        let path = "./data_baseline";
+        // build a genuinely pre-series file by hand: `key_size | val_size |
+        // key | val`, with no file header and no crc/timestamp/store_id per
+        // record — the layout every `./data` file predating this crate's
+        // crc and multi-store support was actually written in
+        let mut raw = Vec::new();
+        for (key, val) in [("baseline_key0", "baseline_val0"), ("baseline_key1", "baseline_val1")] {
+            raw.extend_from_slice(&usize::to_le_bytes(key.len()));
+            raw.extend_from_slice(&usize::to_le_bytes(val.len()));
+            raw.extend_from_slice(key.as_bytes());
+            raw.extend_from_slice(val.as_bytes());
+        }
+        fs::write(path, &raw).unwrap();
+
+        super::upgrade(path).expect("expect upgrade to migrate the legacy layout");
+
+        let mut kv: Kvdb<fs::File> = Kvdb::new();
+        kv.load(path)
+            .expect("expect load to work after migrating the legacy layout");
+        let store = kv.default_store();
+        let val0: TestValue = kv.get(&store, "baseline_key0").unwrap();
+        assert_eq!(val0.value, "baseline_val0");
+        let val1: TestValue = kv.get(&store, "baseline_key1").unwrap();
+        assert_eq!(val1.value, "baseline_val1");
+    }
+
+    #[test]
+    fn upgrade_rejects_unrecognized_layout() {
+        let path = "./data_garbage";
+        fs::write(path, b"this is not a kvdb file in any known layout").unwrap();
+        let err = super::upgrade(path).expect_err("expect upgrade to refuse to guess");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
 }